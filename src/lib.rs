@@ -7,30 +7,53 @@
 //! extern crate enimda;
 //!
 //! use std::path::Path;
-//! use enimda::enimda;
+//! use enimda::{enimda_with_options, EnimdaOptions};
 //!
 //! let path = Path::new("test.jpg");
-//! let borders = enimda(&path, Some(10), Some(512), Some(50), Some(0.25), Some(0.5), Some(false))?;
+//! let options = EnimdaOptions::default().frames(10).size(512).columns(50);
+//! let borders = enimda_with_options(&path, &options)?;
 //!
 //! println!("{:?}", borders);
 //! ```
+//!
+//! Enable the `parallel` feature to scan the four sides of an image, and the frames of an
+//! animated GIF, concurrently using rayon. This is a memory/speed trade-off for GIFs: every
+//! selected frame is decoded up front so the frames can be scanned concurrently, instead of
+//! the default behaviour of decoding and scanning one frame at a time, which bounds peak memory
+//! to roughly one frame regardless of frame count but forgoes the cross-frame speedup.
+//!
+//! [`enimda_from_image`](fn.enimda_from_image.html) and
+//! [`enimda_from_bytes`](fn.enimda_from_bytes.html) scan an already-decoded image or a raw byte
+//! buffer respectively, for callers that already hold the image in memory and want to avoid
+//! redundant disk I/O.
+//!
+//! Use [`EnimdaOptions::sides`](struct.EnimdaOptions.html#method.sides) to restrict the scan to
+//! a subset of the four sides, e.g. to strip only vertical letterboxing.
 
 #![deny(missing_docs)]
+// This crate targets the 2015 edition and uses its bare trait object syntax (`Box<Error + Send +
+// Sync>`) throughout; `dyn` is a 2018+ idiom and switching to it is out of scope here.
+#![allow(bare_trait_objects)]
 
 extern crate rand;
 extern crate image;
 extern crate gif;
 extern crate gif_dispose;
-extern crate image_utils;
+extern crate memmap;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 
+use std::collections::HashSet;
 use std::path::Path;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor, Read};
 use std::error::Error;
-use image::{ImageRgba8, ImageBuffer, ImageFormat};
-use image_utils::info;
-use gif::{Decoder, SetParameter, ColorOutput};
+use memmap::Mmap;
+use image::{GenericImage, ImageRgba8, ImageBuffer, ImageFormat, DynamicImage};
+use gif::{Decoder, Encoder, Frame, Repeat, SetParameter, ColorOutput};
 use gif_dispose::Screen;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 mod utils;
 
@@ -49,6 +72,299 @@ pub struct Borders {
     pub left: u32,
 }
 
+/// Options controlling an [`enimda_with_options`](fn.enimda_with_options.html) scan
+///
+/// Construct with `EnimdaOptions::default()` and chain setters for the fields you want to
+/// override, e.g. `EnimdaOptions::default().size(512).sides([true, false, true, false])` to
+/// fit the image to 512px and only look for top and bottom borders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnimdaOptions {
+    /// Frame limit to use in case of animated image, optimization parameter, no limit by
+    /// default, if set then random frames will be used for scan
+    pub frames: Option<u32>,
+    /// Fit image to this size in pixels to improve performance, optimization parameter, no
+    /// resize by default
+    pub size: Option<u32>,
+    /// Column limit to use for scan, optimization parameter, no limit by default, if set then
+    /// random columns will be used for scan
+    pub columns: Option<u32>,
+    /// Percent of pixels of image height to use for scan, 0.25 by default
+    pub depth: Option<f32>,
+    /// Threshold, aggressiveness of algorithm, 0.5 by default
+    pub threshold: Option<f32>,
+    /// Iteratively find deep borders, true by default (less performant, but more accurate)
+    pub deep: Option<bool>,
+    /// Which sides to scan, in `[top, right, bottom, left]` order; a disabled side is skipped
+    /// and reported as a zero offset, all sides enabled by default
+    pub sides: [bool; 4],
+}
+
+impl Default for EnimdaOptions {
+    fn default() -> EnimdaOptions {
+        EnimdaOptions {
+            frames: None,
+            size: None,
+            columns: None,
+            depth: None,
+            threshold: None,
+            deep: None,
+            sides: [true, true, true, true],
+        }
+    }
+}
+
+impl EnimdaOptions {
+    /// Set the frame limit
+    pub fn frames(mut self, frames: u32) -> EnimdaOptions {
+        self.frames = Some(frames);
+        self
+    }
+
+    /// Set the resize target
+    pub fn size(mut self, size: u32) -> EnimdaOptions {
+        self.size = Some(size);
+        self
+    }
+
+    /// Set the column limit
+    pub fn columns(mut self, columns: u32) -> EnimdaOptions {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Set the scan depth
+    pub fn depth(mut self, depth: f32) -> EnimdaOptions {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Set the algorithm threshold
+    pub fn threshold(mut self, threshold: f32) -> EnimdaOptions {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Set whether deep borders are found iteratively
+    pub fn deep(mut self, deep: bool) -> EnimdaOptions {
+        self.deep = Some(deep);
+        self
+    }
+
+    /// Restrict the scan to the given sides, in `[top, right, bottom, left]` order
+    pub fn sides(mut self, sides: [bool; 4]) -> EnimdaOptions {
+        self.sides = sides;
+        self
+    }
+}
+
+/// Scan a decoded image and find its borders
+///
+/// `im` - already decoded image, e.g. held in memory by a web service
+///
+/// `options` - scan options, see [`EnimdaOptions`](struct.EnimdaOptions.html)
+///
+/// Returns Borders struct
+pub fn enimda_from_image(im: &DynamicImage, options: &EnimdaOptions) -> Result<Borders, Box<Error + Send + Sync>> {
+    let borders = scan(im, options)?;
+
+    Ok(Borders {
+        top: borders[0],
+        right: borders[1],
+        bottom: borders[2],
+        left: borders[3],
+    })
+}
+
+/// Scan raw, undecoded image bytes and find its borders
+///
+/// `buf` - raw image bytes, e.g. a request body or a buffer read from a socket
+///
+/// `format` - format of the bytes in `buf`
+///
+/// `options` - scan options, see [`EnimdaOptions`](struct.EnimdaOptions.html)
+///
+/// Returns Borders struct
+pub fn enimda_from_bytes(buf: &[u8],
+                          format: ImageFormat,
+                          options: &EnimdaOptions)
+                          -> Result<Borders, Box<Error + Send + Sync>> {
+    let borders = match format {
+        ImageFormat::GIF => {
+            let frames = options.frames.unwrap_or(0);
+            // Counting the frames up front requires a full decode pass of its own, so only pay
+            // for it when a frame limit was actually requested.
+            let frameset = if frames > 0 {
+                let mut counter = Decoder::new(Cursor::new(buf));
+                counter.set(ColorOutput::Indexed);
+                let mut counter = counter.read_info()?;
+                let mut total = 0;
+                while counter.read_next_frame()?.is_some() {
+                    total += 1;
+                }
+
+                slice(total, frames)?
+            } else {
+                HashSet::new()
+            };
+
+            let mut decoder = Decoder::new(Cursor::new(buf));
+            decoder.set(ColorOutput::Indexed);
+            let mut reader = decoder.read_info()?;
+            let (width, height) = (reader.width() as u32, reader.height() as u32);
+            let mut screen = Screen::new_reader(&reader);
+
+            // Enabling `parallel` trades the streaming memory bound below for speed: every
+            // selected frame is decoded up front so the frames can be scanned concurrently via
+            // rayon and reduced afterwards, same as chunk0-1's original multi-frame speedup.
+            #[cfg(feature = "parallel")]
+            let borders = {
+                let mut index = 0;
+                let mut ims = Vec::new();
+                while let Some(frame) = reader.read_next_frame()? {
+                    if frames == 0 || frameset.contains(&index) {
+                        screen.blit_frame(frame)?;
+                        let mut pixels: Vec<u8> = Vec::new();
+                        for pixel in screen.pixels.pixels() {
+                            pixels.push(pixel.r);
+                            pixels.push(pixel.g);
+                            pixels.push(pixel.b);
+                            pixels.push(pixel.a);
+                        }
+                        ims.push(ImageRgba8(ImageBuffer::from_raw(width, height, pixels).unwrap()));
+                    }
+
+                    index += 1;
+                }
+
+                let variants = ims.par_iter()
+                    .map(|im| scan(im, options))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut borders = vec![0, 0, 0, 0];
+                for (index, variant) in variants.iter().enumerate() {
+                    for side in 0..borders.len() {
+                        if index == 0 || variant[side] < borders[side] {
+                            borders[side] = variant[side];
+                        }
+                    }
+                }
+
+                borders
+            };
+
+            // Without `parallel`, frames are decoded and scanned one at a time, immediately
+            // reducing into the running per-side minimum, rather than collecting every selected
+            // frame's decoded RGBA buffer into a `Vec` up front. This bounds peak memory to
+            // roughly one frame regardless of how many frames the GIF has or how many are
+            // selected for scan.
+            #[cfg(not(feature = "parallel"))]
+            let borders = {
+                let mut index = 0;
+                let mut borders = vec![0, 0, 0, 0];
+                let mut scanned = 0;
+                while let Some(frame) = reader.read_next_frame()? {
+                    if frames == 0 || frameset.contains(&index) {
+                        screen.blit_frame(frame)?;
+                        let mut pixels: Vec<u8> = Vec::new();
+                        for pixel in screen.pixels.pixels() {
+                            pixels.push(pixel.r);
+                            pixels.push(pixel.g);
+                            pixels.push(pixel.b);
+                            pixels.push(pixel.a);
+                        }
+                        let im = ImageRgba8(ImageBuffer::from_raw(width, height, pixels).unwrap());
+                        let variant = scan(&im, options)?;
+
+                        for side in 0..borders.len() {
+                            if scanned == 0 || variant[side] < borders[side] {
+                                borders[side] = variant[side];
+                            }
+                        }
+                        scanned += 1;
+                    }
+
+                    index += 1;
+                }
+
+                borders
+            };
+
+            borders
+        }
+        _ => {
+            let im = image::load_from_memory_with_format(buf, format)?;
+            scan(&im, options)?
+        }
+    };
+
+    Ok(Borders {
+        top: borders[0],
+        right: borders[1],
+        bottom: borders[2],
+        left: borders[3],
+    })
+}
+
+// Format and dimensions of a source file, read up front so `enimda_with_options`/`trim` can pick
+// the right decode path (GIF frames need `gif_dispose` rather than `image::load`) before they
+// commit to one.
+struct Info {
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+}
+
+// The format is sniffed from a small header read rather than from the whole file. For non-GIF
+// formats the dimensions still come from a full `image::load`, so a caller that decodes the image
+// afterwards decodes it twice.
+fn info(path: &Path) -> Result<Info, Box<Error + Send + Sync>> {
+    let mut header = Vec::new();
+    File::open(path)?.take(512).read_to_end(&mut header)?;
+    let format = image::guess_format(&header)?;
+
+    let (width, height) = match format {
+        ImageFormat::GIF => {
+            let mut decoder = Decoder::new(File::open(path)?);
+            decoder.set(ColorOutput::Indexed);
+            let reader = decoder.read_info()?;
+            (reader.width() as u32, reader.height() as u32)
+        }
+        _ => image::load(BufReader::new(File::open(path)?), format)?.dimensions(),
+    };
+
+    Ok(Info { format, width, height })
+}
+
+/// Scan image and find its borders
+///
+/// `path` - path to image file
+///
+/// `options` - scan options, see [`EnimdaOptions`](struct.EnimdaOptions.html)
+///
+/// Returns Borders struct
+pub fn enimda_with_options(path: &Path,
+                           options: &EnimdaOptions)
+                           -> Result<Borders, Box<Error + Send + Sync>> {
+    let inf = info(path)?;
+
+    match inf.format {
+        ImageFormat::GIF => {
+            // Large animated GIFs are mapped rather than read into a heap-allocated `Vec<u8>`,
+            // so the encoded bytes stay backed by the OS page cache instead of being copied.
+            let file = File::open(path)?;
+            // Safety: `file` is not truncated or otherwise modified for as long as `mmap` is
+            // alive (it's a local file we just opened and nothing else holds a handle to it),
+            // so the mapping can't be read into past its backing data.
+            let mmap = unsafe { Mmap::map(&file)? };
+            enimda_from_bytes(&mmap, inf.format, options)
+        }
+        _ => {
+            let im = image::load(BufReader::new(File::open(path)?), inf.format)?;
+            enimda_from_image(&im, options)
+        }
+    }
+}
+
 /// Scan image and find its borders
 ///
 /// `path` - path to image file
@@ -69,6 +385,7 @@ pub struct Borders {
 /// `deep` - iteratively find deep borders, true by default (less performant, but more accurate)
 ///
 /// Returns Borders struct
+#[deprecated(since = "0.4.0", note = "use `enimda_with_options` with an `EnimdaOptions` instead")]
 pub fn enimda(path: &Path,
               frames: Option<u32>,
               size: Option<u32>,
@@ -76,60 +393,387 @@ pub fn enimda(path: &Path,
               depth: Option<f32>,
               threshold: Option<f32>,
               deep: Option<bool>)
-              -> Result<Borders, Box<Error>> {
+              -> Result<Borders, Box<Error + Send + Sync>> {
+    enimda_with_options(path, &legacy_options(frames, size, columns, depth, threshold, deep))
+}
+
+// Builds an `EnimdaOptions` from the deprecated positional parameters shared by `enimda` and
+// `detect_and_trim`.
+fn legacy_options(frames: Option<u32>,
+                   size: Option<u32>,
+                   columns: Option<u32>,
+                   depth: Option<f32>,
+                   threshold: Option<f32>,
+                   deep: Option<bool>)
+                   -> EnimdaOptions {
+    EnimdaOptions {
+        frames,
+        size,
+        columns,
+        depth,
+        threshold,
+        deep,
+        ..EnimdaOptions::default()
+    }
+}
+
+/// Crop an image using previously detected borders and write the result to `out`
+///
+/// `path` - path to the source image file
+///
+/// `out` - path to write the cropped image to
+///
+/// `borders` - borders to crop off, typically produced by
+/// [`enimda_with_options`](fn.enimda_with_options.html)
+///
+/// Static images are cropped and saved in their original format. Animated GIFs have every frame
+/// cropped and are re-encoded into a new animation, preserving each frame's delay and disposal
+/// method, as well as the source's loop count (or lack of one).
+pub fn trim(path: &Path,
+            out: &Path,
+            borders: &Borders)
+            -> Result<(), Box<Error + Send + Sync>> {
     let inf = info(path)?;
+    check_borders(borders, inf.width, inf.height)?;
 
-    let borders = match inf.format {
-        ImageFormat::GIF => {
-            let frames = frames.unwrap_or(0);
-            let frameset = slice(inf.frames, frames)?;
+    match inf.format {
+        ImageFormat::GIF => trim_gif(path, out, borders, inf.width, inf.height),
+        _ => {
+            let mut im = image::load(BufReader::new(File::open(path)?), inf.format)?;
+            let (w, h) = im.dimensions();
+            let cropped = im.crop(borders.left,
+                                   borders.top,
+                                   w - borders.left - borders.right,
+                                   h - borders.top - borders.bottom);
+            let mut file = File::create(out)?;
+            cropped.write_to(&mut file, inf.format)?;
 
-            let mut decoder = Decoder::new(File::open(path)?);
-            decoder.set(ColorOutput::Indexed);
-            let mut reader = decoder.read_info().unwrap();
-            let mut screen = Screen::new(&reader);
-
-            let mut index = 0;
-            let mut variants = Vec::new();
-            while let Some(frame) = reader.read_next_frame().unwrap() {
-                if frames == 0 || frameset.contains(&index) {
-                    screen.blit(&frame)?;
-                    let mut buf: Vec<u8> = Vec::new();
-                    for pixel in screen.pixels.iter() {
-                        buf.push(pixel.r);
-                        buf.push(pixel.g);
-                        buf.push(pixel.b);
-                        buf.push(pixel.a);
-                    }
-                    let im = ImageRgba8(ImageBuffer::from_raw(inf.width, inf.height, buf).unwrap());
-                    let sub = scan(&im, size, columns, depth, threshold, deep)?;
-                    variants.push(sub);
-                }
+            Ok(())
+        }
+    }
+}
+
+// Guards the unchecked subtractions in `trim`/`trim_gif`, which would otherwise underflow (or,
+// in release builds, wrap into a huge crop size) for borders that don't fit inside the image.
+fn check_borders(borders: &Borders, width: u32, height: u32) -> Result<(), Box<Error + Send + Sync>> {
+    if borders.left + borders.right >= width || borders.top + borders.bottom >= height {
+        return Err(format!("borders {:?} do not fit within a {}x{} image", borders, width, height).into());
+    }
+
+    Ok(())
+}
+
+// The `gif` decoder doesn't expose the source's NETSCAPE2.0 looping extension, so its raw bytes
+// are scanned for it directly. `None` means the source has no such extension and plays once;
+// `trim_gif` must preserve that instead of always looping the cropped output forever.
+fn source_repeat(path: &Path) -> Result<Option<Repeat>, Box<Error + Send + Sync>> {
+    let mut buf = Vec::new();
+    File::open(path)?.read_to_end(&mut buf)?;
+
+    // Extension Introducer (0x21), Application Extension Label (0xFF), Block Size (0x0B), then
+    // the application identifier; matching all of this, not just the identifier, avoids
+    // misreading an unrelated comment/text extension that happens to contain the literal bytes
+    // "NETSCAPE2.0".
+    const MARKER: &[u8] = b"\x21\xFF\x0BNETSCAPE2.0";
+    let data = match buf.windows(MARKER.len()).position(|w| w == MARKER) {
+        Some(pos) => pos + MARKER.len(),
+        None => return Ok(None),
+    };
 
-                index += 1;
+    // Sub-block layout: size (0x03), sub-block id (0x01), then a little-endian loop count;
+    // 0 conventionally means infinite.
+    if buf.len() < data + 4 || buf[data] != 0x03 || buf[data + 1] != 0x01 {
+        return Ok(None);
+    }
+
+    let count = u16::from(buf[data + 2]) | (u16::from(buf[data + 3]) << 8);
+
+    Ok(Some(if count == 0 {
+        Repeat::Infinite
+    } else {
+        Repeat::Finite(count)
+    }))
+}
+
+fn trim_gif(path: &Path,
+            out: &Path,
+            borders: &Borders,
+            width: u32,
+            height: u32)
+            -> Result<(), Box<Error + Send + Sync>> {
+    let crop_width = width - borders.left - borders.right;
+    let crop_height = height - borders.top - borders.bottom;
+
+    let mut decoder = Decoder::new(File::open(path)?);
+    decoder.set(ColorOutput::Indexed);
+    let mut reader = decoder.read_info()?;
+    let mut screen = Screen::new_reader(&reader);
+
+    let mut writer = File::create(out)?;
+    let mut encoder = Encoder::new(&mut writer, crop_width as u16, crop_height as u16, &[])?;
+    if let Some(repeat) = source_repeat(path)? {
+        encoder.set(repeat)?;
+    }
+
+    while let Some(frame) = reader.read_next_frame()? {
+        screen.blit_frame(frame)?;
+
+        let mut pixels: Vec<u8> = Vec::with_capacity((crop_width * crop_height * 4) as usize);
+        for y in borders.top..(height - borders.bottom) {
+            for x in borders.left..(width - borders.right) {
+                let pixel = &screen.pixels.buf()[(y * width + x) as usize];
+                pixels.push(pixel.r);
+                pixels.push(pixel.g);
+                pixels.push(pixel.b);
+                pixels.push(pixel.a);
             }
+        }
 
-            let mut borders = vec![0, 0, 0, 0];
-            for (index, variant) in variants.iter().enumerate() {
-                for side in 0..borders.len() {
-                    if index == 0 || variant[side] < borders[side] {
-                        borders[side] = variant[side];
-                    }
-                }
+        let mut out_frame = Frame::from_rgba_speed(crop_width as u16, crop_height as u16, &mut pixels, 10);
+        out_frame.delay = frame.delay;
+        out_frame.dispose = frame.dispose;
+        encoder.write_frame(&out_frame)?;
+    }
+
+    Ok(())
+}
+
+/// Detect an image's borders and crop it in one step
+///
+/// Convenience wrapper combining [`enimda_with_options`](fn.enimda_with_options.html) and
+/// [`trim`](fn.trim.html); see [`EnimdaOptions`](struct.EnimdaOptions.html) for the meaning of
+/// the scan options.
+///
+/// Returns the detected Borders that were applied
+pub fn detect_and_trim_with_options(path: &Path,
+                                     out: &Path,
+                                     options: &EnimdaOptions)
+                                     -> Result<Borders, Box<Error + Send + Sync>> {
+    let borders = enimda_with_options(path, options)?;
+    trim(path, out, &borders)?;
+
+    Ok(borders)
+}
+
+/// Detect an image's borders and crop it in one step
+///
+/// Convenience wrapper combining [`enimda`](fn.enimda.html) and [`trim`](fn.trim.html); see
+/// `enimda` for the meaning of the scan parameters.
+///
+/// Returns the detected Borders that were applied
+#[deprecated(since = "0.4.0",
+             note = "use `detect_and_trim_with_options` with an `EnimdaOptions` instead")]
+#[allow(clippy::too_many_arguments)]
+pub fn detect_and_trim(path: &Path,
+                        out: &Path,
+                        frames: Option<u32>,
+                        size: Option<u32>,
+                        columns: Option<u32>,
+                        depth: Option<f32>,
+                        threshold: Option<f32>,
+                        deep: Option<bool>)
+                        -> Result<Borders, Box<Error + Send + Sync>> {
+    let options = legacy_options(frames, size, columns, depth, threshold, deep);
+    let borders = enimda_with_options(path, &options)?;
+    trim(path, out, &borders)?;
+
+    Ok(borders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use image::{ImageRgb8, Rgb};
+
+    // A uniform border (zero entropy) around a checkerboard center (non-zero entropy), so `scan`
+    // (driven through `enimda_from_image`/`enimda_from_bytes` here) has an actual transition to
+    // detect on every side instead of scanning a flat image throughout.
+    fn bordered_image(size: u32, border: u32) -> DynamicImage {
+        ImageRgb8(ImageBuffer::from_fn(size, size, |x, y| {
+            let inside = x >= border && x < size - border && y >= border && y < size - border;
+            if inside && (x + y) % 2 == 0 {
+                Rgb([64, 64, 64])
+            } else if inside {
+                Rgb([192, 192, 192])
+            } else {
+                Rgb([128, 128, 128])
             }
+        }))
+    }
 
-            borders
+    #[test]
+    fn enimda_from_image_finds_a_nonzero_border_on_every_side() {
+        let im = bordered_image(64, 8);
+        let options = EnimdaOptions::default().size(64);
+
+        let borders = enimda_from_image(&im, &options).unwrap();
+
+        assert!(borders.top > 0);
+        assert!(borders.right > 0);
+        assert!(borders.bottom > 0);
+        assert!(borders.left > 0);
+    }
+
+    #[test]
+    fn enimda_from_bytes_finds_a_nonzero_border_on_every_side() {
+        let im = bordered_image(64, 8);
+        let mut buf = Cursor::new(Vec::new());
+        im.write_to(&mut buf, ImageFormat::PNG).unwrap();
+        let options = EnimdaOptions::default().size(64);
+
+        let borders = enimda_from_bytes(buf.get_ref(), ImageFormat::PNG, &options).unwrap();
+
+        assert!(borders.top > 0);
+        assert!(borders.right > 0);
+        assert!(borders.bottom > 0);
+        assert!(borders.left > 0);
+    }
+
+    // Raw RGBA pixels for `bordered_image`, flattened the way `Frame::from_rgba_speed` expects.
+    fn bordered_rgba(size: u32, border: u32) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let inside = x >= border && x < size - border && y >= border && y < size - border;
+                let gray = if inside && (x + y) % 2 == 0 {
+                    64
+                } else if inside {
+                    192
+                } else {
+                    128
+                };
+                pixels.extend_from_slice(&[gray, gray, gray, 255]);
+            }
         }
-        _ => {
-            let im = image::load(BufReader::new(File::open(path)?), inf.format)?;
-            scan(&im, size, columns, depth, threshold, deep)?
+
+        pixels
+    }
+
+    // Encodes a two-frame GIF whose frames have different border widths, so the GIF reduction
+    // path (parallel or sequential, whichever is compiled in) has to pick the smallest border
+    // seen across frames rather than just the last one decoded.
+    fn two_frame_gif(size: u32, borders: [u32; 2]) -> Vec<u8> {
+        let mut buf = Cursor::new(Vec::new());
+        {
+            let mut encoder = Encoder::new(&mut buf, size as u16, size as u16, &[]).unwrap();
+            for border in &borders {
+                let mut pixels = bordered_rgba(size, *border);
+                let frame = Frame::from_rgba_speed(size as u16, size as u16, &mut pixels, 10);
+                encoder.write_frame(&frame).unwrap();
+            }
         }
-    };
 
-    Ok(Borders {
-        top: borders[0],
-        right: borders[1],
-        bottom: borders[2],
-        left: borders[3],
-    })
+        buf.into_inner()
+    }
+
+    // Whichever reduction path is compiled in (`parallel`'s rayon-scanned variants, or the
+    // sequential running-minimum from chunk0-5), scanning a multi-frame GIF must keep the
+    // smallest border seen across frames on every side, not the border of any single frame.
+    #[test]
+    fn enimda_from_bytes_reduces_gif_frames_to_their_smallest_shared_border() {
+        let bytes = two_frame_gif(64, [6, 8]);
+        let options = EnimdaOptions::default().size(64);
+
+        let borders = enimda_from_bytes(&bytes, ImageFormat::GIF, &options).unwrap();
+
+        assert!(borders.top > 0);
+        assert!(borders.right > 0);
+        assert!(borders.bottom > 0);
+        assert!(borders.left > 0);
+        assert!(borders.top < 9);
+        assert!(borders.right < 9);
+        assert!(borders.bottom < 9);
+        assert!(borders.left < 9);
+    }
+
+    #[test]
+    fn check_borders_rejects_borders_wider_than_the_image() {
+        let borders = Borders {
+            top: 0,
+            right: 0,
+            bottom: 0,
+            left: 100,
+        };
+
+        assert!(check_borders(&borders, 50, 50).is_err());
+    }
+
+    #[test]
+    fn check_borders_accepts_borders_that_fit() {
+        let borders = Borders {
+            top: 1,
+            right: 1,
+            bottom: 1,
+            left: 1,
+        };
+
+        assert!(check_borders(&borders, 50, 50).is_ok());
+    }
+
+    fn with_temp_file(name: &str, contents: &[u8]) -> Result<Option<Repeat>, Box<Error + Send + Sync>> {
+        let path = env::temp_dir().join(name);
+        fs::write(&path, contents)?;
+        let result = source_repeat(&path);
+        fs::remove_file(&path)?;
+
+        result
+    }
+
+    #[test]
+    fn source_repeat_finds_a_finite_loop_count() {
+        let mut bytes = b"junk before it".to_vec();
+        bytes.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        bytes.extend_from_slice(b"NETSCAPE2.0");
+        bytes.extend_from_slice(&[0x03, 0x01, 0x05, 0x00]);
+
+        let repeat = with_temp_file("source_repeat_finds_a_finite_loop_count.gif", &bytes).unwrap();
+
+        assert!(matches!(repeat, Some(Repeat::Finite(5))));
+    }
+
+    #[test]
+    fn source_repeat_finds_an_infinite_loop_count() {
+        let mut bytes = vec![0x21, 0xFF, 0x0B];
+        bytes.extend_from_slice(b"NETSCAPE2.0");
+        bytes.extend_from_slice(&[0x03, 0x01, 0x00, 0x00]);
+
+        let repeat = with_temp_file("source_repeat_finds_an_infinite_loop_count.gif", &bytes).unwrap();
+
+        assert!(matches!(repeat, Some(Repeat::Infinite)));
+    }
+
+    #[test]
+    fn source_repeat_is_none_without_the_extension() {
+        let bytes = b"just some GIF bytes with no looping extension".to_vec();
+
+        let repeat = with_temp_file("source_repeat_is_none_without_the_extension.gif", &bytes).unwrap();
+
+        assert!(repeat.is_none());
+    }
+
+    #[test]
+    fn source_repeat_is_none_when_the_sub_block_is_truncated() {
+        let mut bytes = vec![0x21, 0xFF, 0x0B];
+        bytes.extend_from_slice(b"NETSCAPE2.0");
+        bytes.extend_from_slice(&[0x03, 0x01]);
+
+        let repeat = with_temp_file("source_repeat_is_none_when_the_sub_block_is_truncated.gif", &bytes).unwrap();
+
+        assert!(repeat.is_none());
+    }
+
+    // A "NETSCAPE2.0" string not preceded by the Application Extension's introducer/label/size
+    // bytes is just incidental text (e.g. inside an unrelated comment extension), not a real
+    // loop-count block, and must not be misread as one.
+    #[test]
+    fn source_repeat_ignores_the_marker_without_its_extension_header() {
+        let bytes = b"a comment block that happens to mention NETSCAPE2.0 by name".to_vec();
+
+        let repeat = with_temp_file("source_repeat_ignores_the_marker_without_its_extension_header.gif",
+                                     &bytes)
+            .unwrap();
+
+        assert!(repeat.is_none());
+    }
 }